@@ -16,6 +16,17 @@ pub fn get_cli<'a, 'b>() -> App<'a, 'b> {
                         .help("The target to backup (if excluded backs up all non-optional targets)")
                         .index(1),
                 )
+                .arg(
+                    Arg::with_name("dry-run")
+                        .help("Report what would be copied without touching the destination")
+                        .long("dry-run")
+                )
+                .arg(
+                    Arg::with_name("verbose")
+                        .help("Print every file as it's copied")
+                        .short("v")
+                        .long("verbose")
+                )
         )
         .subcommand(
             SubCommand::with_name("restore")
@@ -27,6 +38,22 @@ pub fn get_cli<'a, 'b>() -> App<'a, 'b> {
                         .required(true)
                 )
         )
+        .subcommand(
+            SubCommand::with_name("mount")
+                .about("mount a target's retained snapshots read-only via FUSE")
+                .arg(
+                    Arg::with_name("TARGET")
+                        .help("The tagged target whose snapshots to mount")
+                        .index(1)
+                        .required(true)
+                )
+                .arg(
+                    Arg::with_name("MOUNTPOINT")
+                        .help("Where to mount the read-only snapshot filesystem")
+                        .index(2)
+                        .required(true)
+                )
+        )
         .subcommand(
             SubCommand::with_name("completion")
                 .about("generate shell completions")
@@ -36,6 +36,10 @@ pub struct BackupTarget {
     pub ignore_files: Vec<String>,
     #[serde(default = "Vec::new")]
     pub ignore_folders: Vec<String>,
+    #[serde(default = "Vec::new")]
+    pub allowed_extensions: Vec<String>,
+    #[serde(default = "Vec::new")]
+    pub excluded_extensions: Vec<String>,
     pub target_path: PathBuf,
     #[serde(default = "default_optional")]
     pub optional: bool,
@@ -43,10 +47,104 @@ pub struct BackupTarget {
     pub keep_num: i32,
     #[serde(default = "default_always_copy")]
     pub always_copy: bool,
+    #[serde(default = "default_mode")]
+    pub mode: BackupMode,
+    #[serde(default = "default_suffix")]
+    pub suffix: String,
+    #[serde(default = "default_compare")]
+    pub compare: CompareMode,
+    #[serde(default = "default_compare_contents")]
+    pub compare_contents: bool,
+    #[serde(default = "default_preserve")]
+    pub preserve: Vec<Preserve>,
+    #[serde(default = "default_format")]
+    pub format: ArchiveFormat,
+    #[serde(default = "default_compression_level")]
+    pub compression_level: i32,
+    #[serde(default = "default_compression_window")]
+    pub compression_window: u32,
+    #[serde(default = "default_compression")]
+    pub compression: CompressionMode,
+    #[serde(default = "default_dedup")]
+    pub dedup: bool,
+    #[serde(default = "default_ignore_hierarchical")]
+    pub ignore_hierarchical: bool,
+    #[serde(default = "default_respect_gitignore")]
+    pub respect_gitignore: bool,
+    #[serde(default = "default_atomic_writes")]
+    pub atomic_writes: bool,
     #[serde(flatten)]
     pub additional_options: Option<Additional>,
 }
 
+/// The on-disk layout a target is written in
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveFormat {
+    /// Mirror the source as a plain directory tree (the default)
+    Tree,
+    /// Stream the tree into a single zstd-compressed tar archive
+    TarZstd,
+    /// Stream the tree into a single xz-compressed tar archive
+    TarXz,
+}
+
+/// A piece of file metadata that should be carried over from the source
+/// to the destination after a copy
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Preserve {
+    /// The source's modification/access times
+    Mtime,
+    /// The source's Unix permission bits
+    Mode,
+    /// The source's Unix uid/gid (requires running privileged)
+    Ownership,
+    /// The source's extended attributes (Unix only)
+    Xattr,
+}
+
+/// Optional transparent per-file compression applied within a `Tree`-format
+/// target, using `compression_level`/`compression_window` the same way
+/// `TarZstd`/`TarXz` archives do
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum CompressionMode {
+    /// Store files as-is (the default)
+    None,
+    /// Compress each file individually with zstd, writing `<name>.zst`
+    Zstd,
+    /// Compress each file individually with xz, writing `<name>.xz`
+    Xz,
+}
+
+/// How a target decides whether a destination file is up to date
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum CompareMode {
+    /// Skip the copy when the destination is not older than the source
+    Timestamp,
+    /// Skip the copy when the destination is byte-identical to the source
+    Content,
+    /// Skip the copy unless the source is newer *and* its content differs
+    Both,
+}
+
+/// The rotation strategy applied to a destination file that is
+/// about to be overwritten
+#[derive(Deserialize, PartialEq, Debug, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupMode {
+    /// Overwrite the destination with no rotation
+    None,
+    /// Rename the destination to `dest<suffix>` before overwriting
+    Simple,
+    /// Keep numbered versions `dest.~1~`, `dest.~2~`, ... up to `keep_num`
+    Numbered,
+    /// `Numbered` if a numbered backup already exists, otherwise `Simple`
+    Existing,
+}
+
 #[derive(Deserialize, PartialEq, Debug)]
 #[serde(untagged)]
 pub enum Additional {
@@ -57,15 +155,30 @@ pub enum Additional {
 pub struct SharedOptions {}
 
 impl BackupTarget {
-    pub fn backup(self) -> std::io::Result<i32> {
-        if let Some(ado) = self.additional_options {
+    /// Backs up the target: a network target is streamed over TLS
+    /// (`dry_run`/`verbose` don't apply there, since the protocol has no
+    /// reporting-only mode), anything else is copied locally via
+    /// `operation::copy_to_target`, threaded across the available cores
+    pub fn backup(self, dry_run: bool, verbose: bool) -> std::io::Result<i32> {
+        if let Some(ado) = &self.additional_options {
             match ado {
-                Additional::Network { .. } => unimplemented!(),
+                Additional::Network { url, password } => {
+                    crate::operation::network_copy(&self, url, password)
+                }
             }
         } else {
-            crate::operations::local_copy(self)
+            let threads = std::thread::available_parallelism()
+                .map(|n| n.get() as i32)
+                .unwrap_or(1);
+            crate::operation::copy_to_target(&self, threads, dry_run, verbose)
         }
     }
+
+    /// Restores the target's most recent backup (or its single flat copy,
+    /// when `keep_num == 1`) back onto its original `path`
+    pub fn restore(&self) -> std::io::Result<i32> {
+        crate::operation::restore_target(self)
+    }
 }
 
 // ***************
@@ -109,6 +222,64 @@ const fn default_always_copy() -> bool {
     false
 }
 
+const fn default_mode() -> BackupMode {
+    BackupMode::None
+}
+
+fn default_suffix() -> String {
+    "~".to_owned()
+}
+
+const fn default_compare() -> CompareMode {
+    CompareMode::Timestamp
+}
+
+// Disabled by default: it trades extra reads for fewer writes, which only
+// pays off on slow/networked destinations or when minimizing write wear
+const fn default_compare_contents() -> bool {
+    false
+}
+
+fn default_preserve() -> Vec<Preserve> {
+    vec![Preserve::Mtime]
+}
+
+const fn default_format() -> ArchiveFormat {
+    ArchiveFormat::Tree
+}
+
+const fn default_compression_level() -> i32 {
+    3
+}
+
+// Larger than the zstd/xz library defaults, trading memory for a better
+// ratio on the large trees this crate typically backs up
+const fn default_compression_window() -> u32 {
+    27
+}
+
+const fn default_compression() -> CompressionMode {
+    CompressionMode::None
+}
+
+const fn default_dedup() -> bool {
+    false
+}
+
+const fn default_ignore_hierarchical() -> bool {
+    true
+}
+
+const fn default_respect_gitignore() -> bool {
+    false
+}
+
+// On by default: a file staged at a temp path and renamed into place can
+// never be observed half-written, which is worth the extra rename syscall
+const fn default_atomic_writes() -> bool {
+    true
+}
+
 impl Default for Config {
     fn default() -> Config {
         Config {
@@ -126,7 +297,7 @@ impl Default for Config {
 ///
 /// # Parameters
 /// - config_path: the PathBuf (or just path) to the
-/// target config file
+///   target config file
 ///
 /// # Note
 /// if the config is invalid the default
@@ -143,9 +314,9 @@ pub fn load_config(config_path: PathBuf) -> Config {
 
 pub fn get_config_folder() -> PathBuf {
     if let Some(project_dirs) = ProjectDirs::from("com", "System.rat", "backup-rat") {
-        return PathBuf::from(project_dirs.config_dir());
+        PathBuf::from(project_dirs.config_dir())
     } else {
-        return PathBuf::new();
+        PathBuf::new()
     }
 }
 
@@ -161,9 +332,24 @@ fn loading_from_string() {
             target_path: PathBuf::from("/mnt/backup"),
             keep_num: 1,
             always_copy: false,
+            mode: BackupMode::None,
+            suffix: "~".to_owned(),
+            compare: CompareMode::Timestamp,
+            compare_contents: false,
+            preserve: vec![Preserve::Mtime],
+            format: ArchiveFormat::Tree,
+            compression_level: 3,
+            compression_window: 27,
+            compression: CompressionMode::None,
+            dedup: false,
+            ignore_hierarchical: true,
+            respect_gitignore: false,
+            atomic_writes: true,
             optional: false,
             ignore_files: Vec::new(),
             ignore_folders: Vec::new(),
+            allowed_extensions: Vec::new(),
+            excluded_extensions: Vec::new(),
             additional_options: Some(Additional::Network {
                 // Not an actual url mind you
                 url: "www.test.com".to_owned(),
@@ -0,0 +1,196 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at http://mozilla.org/MPL/2.0/.
+
+//! Exposes a target's timestamped snapshot directories (kept on disk when
+//! `keep_num > 1`) as a read-only, browsable filesystem via FUSE, so a
+//! single file can be pulled out of any retained point in time without
+//! running a full `restore`.
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use fuser::{
+    FileAttr, FileType, Filesystem, MountOption, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry,
+    ReplyOpen, Request,
+};
+use libc::ENOENT;
+
+const TTL: Duration = Duration::from_secs(1);
+const ROOT_INODE: u64 = 1;
+
+/// A read-only FUSE filesystem whose root lists every snapshot timestamp
+/// directory kept for a target, and whose subtrees mirror that snapshot's
+/// files directly off disk, since the whole point is to browse them
+/// without copying anything out first
+pub struct SnapshotFs {
+    inode_paths: HashMap<u64, PathBuf>,
+    path_inodes: HashMap<PathBuf, u64>,
+    next_inode: u64,
+}
+
+impl SnapshotFs {
+    pub fn new(root: PathBuf) -> Self {
+        let mut inode_paths = HashMap::new();
+        let mut path_inodes = HashMap::new();
+        inode_paths.insert(ROOT_INODE, root.clone());
+        path_inodes.insert(root, ROOT_INODE);
+        SnapshotFs {
+            inode_paths,
+            path_inodes,
+            next_inode: ROOT_INODE + 1,
+        }
+    }
+
+    /// Looks up the inode for a real path, minting a new one the first
+    /// time that path is seen
+    fn inode_for(&mut self, path: &Path) -> u64 {
+        if let Some(&inode) = self.path_inodes.get(path) {
+            return inode;
+        }
+        let inode = self.next_inode;
+        self.next_inode += 1;
+        self.inode_paths.insert(inode, path.to_path_buf());
+        self.path_inodes.insert(path.to_path_buf(), inode);
+        inode
+    }
+
+    /// Builds a `FileAttr` straight from the backing path's real metadata,
+    /// with the write bits stripped since the mount is read-only
+    fn attr(path: &Path, inode: u64) -> Option<FileAttr> {
+        let meta = fs::metadata(path).ok()?;
+        let kind = if meta.is_dir() {
+            FileType::Directory
+        } else {
+            FileType::RegularFile
+        };
+        Some(FileAttr {
+            ino: inode,
+            size: meta.len(),
+            blocks: (meta.len() + 511) / 512,
+            atime: meta.accessed().unwrap_or(std::time::UNIX_EPOCH),
+            mtime: meta.modified().unwrap_or(std::time::UNIX_EPOCH),
+            ctime: meta.modified().unwrap_or(std::time::UNIX_EPOCH),
+            crtime: std::time::UNIX_EPOCH,
+            kind,
+            perm: (meta.mode() & 0o555) as u16,
+            nlink: 1,
+            uid: meta.uid(),
+            gid: meta.gid(),
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        })
+    }
+}
+
+impl Filesystem for SnapshotFs {
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let parent_path = match self.inode_paths.get(&parent) {
+            Some(path) => path.clone(),
+            None => return reply.error(ENOENT),
+        };
+        let child_path = parent_path.join(name);
+        if !child_path.exists() {
+            return reply.error(ENOENT);
+        }
+        let inode = self.inode_for(&child_path);
+        match Self::attr(&child_path, inode) {
+            Some(attr) => reply.entry(&TTL, &attr, 0),
+            None => reply.error(ENOENT),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyAttr) {
+        let path = match self.inode_paths.get(&ino) {
+            Some(path) => path.clone(),
+            None => return reply.error(ENOENT),
+        };
+        match Self::attr(&path, ino) {
+            Some(attr) => reply.attr(&TTL, &attr),
+            None => reply.error(ENOENT),
+        }
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        let path = match self.inode_paths.get(&ino) {
+            Some(path) => path.clone(),
+            None => return reply.error(ENOENT),
+        };
+        let mut entries = vec![
+            (ino, FileType::Directory, ".".to_owned()),
+            (ino, FileType::Directory, "..".to_owned()),
+        ];
+        if let Ok(dir_entries) = fs::read_dir(&path) {
+            for entry in dir_entries.filter_map(|entry| entry.ok()) {
+                let entry_path = entry.path();
+                let kind = if entry_path.is_dir() {
+                    FileType::Directory
+                } else {
+                    FileType::RegularFile
+                };
+                let inode = self.inode_for(&entry_path);
+                entries.push((inode, kind, entry.file_name().to_string_lossy().into_owned()));
+            }
+        }
+        for (i, (inode, kind, name)) in entries.into_iter().enumerate().skip(offset as usize) {
+            if reply.add(inode, (i + 1) as i64, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+
+    fn open(&mut self, _req: &Request<'_>, _ino: u64, _flags: i32, reply: ReplyOpen) {
+        // read-only: there's no per-handle state, every read re-reads the
+        // backing file straight from its real path
+        reply.opened(0, 0);
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        let path = match self.inode_paths.get(&ino) {
+            Some(path) => path.clone(),
+            None => return reply.error(ENOENT),
+        };
+        match fs::read(&path) {
+            Ok(data) => {
+                let start = (offset as usize).min(data.len());
+                let end = start.saturating_add(size as usize).min(data.len());
+                reply.data(&data[start..end]);
+            }
+            Err(_) => reply.error(ENOENT),
+        }
+    }
+}
+
+/// Mounts `root` (a target's snapshot directory, containing one
+/// subdirectory per retained timestamp) read-only at `mountpoint`,
+/// blocking until the filesystem is unmounted
+pub fn mount(root: PathBuf, mountpoint: &Path) -> std::io::Result<()> {
+    let options = [
+        MountOption::RO,
+        MountOption::FSName("backup-rat".to_owned()),
+    ];
+    fuser::mount2(SnapshotFs::new(root), mountpoint, &options)
+}
@@ -6,7 +6,6 @@
 //! all of the actual backup code
 extern crate regex;
 extern crate serde;
-#[macro_use]
 extern crate serde_derive;
 extern crate toml;
 
@@ -2,12 +2,14 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at http://mozilla.org/MPL/2.0/.
 mod cli;
-mod config;
-mod operations;
+#[cfg(feature = "fuse")]
+mod fuse_mount;
 
 use std::io::prelude::*;
+#[cfg(feature = "fuse")]
+use std::path::Path;
 
-use crate::config::{get_config_folder, load_config};
+use backup_rat::config::{self, get_config_folder, load_config, BackupTarget};
 
 fn main() {
     // Reads the command-line arguments using clap
@@ -17,6 +19,8 @@ fn main() {
 
     if let Some(options) = options.subcommand_matches("backup") {
         let mut has_targets = false;
+        let dry_run = options.is_present("dry-run");
+        let verbose = options.is_present("verbose");
         println!(
             r"
     /¯¯\          /¯¯\
@@ -39,12 +43,7 @@ fn main() {
                         has_targets = true;
                         print!("Backing up target: {}... ", tag);
                         flush();
-                        let res = target.backup();
-                        if let Ok(num) = res {
-                            println!("Done: {} files copied.", num);
-                        } else {
-                            println!(" Error: {}", res.unwrap_err());
-                        }
+                        run_target(target, dry_run, verbose);
                     }
                 }
             }
@@ -62,12 +61,7 @@ fn main() {
                     print!("{}... ", &target.path.display());
                 }
                 flush();
-                let res = target.backup();
-                if let Ok(num) = res {
-                    println!("Done: {} files copied.", num);
-                } else {
-                    println!(" Error: {}", res.unwrap_err());
-                }
+                run_target(target, dry_run, verbose);
             }
         }
         if has_targets {
@@ -75,6 +69,40 @@ fn main() {
         } else {
             println!("No targets!");
         }
+    } else if let Some(options) = options.subcommand_matches("mount") {
+        #[cfg(feature = "fuse")]
+        {
+            let target_str = options.value_of("TARGET").unwrap();
+            let mountpoint = options.value_of("MOUNTPOINT").unwrap();
+            let mut found = false;
+            for target in config.targets {
+                if let Some(tag) = &target.tag {
+                    if tag == target_str {
+                        found = true;
+                        if target.keep_num <= 1 {
+                            println!(
+                                "'{}' keeps no timestamped snapshots (keep_num <= 1); there's nothing to mount",
+                                tag
+                            );
+                            continue;
+                        }
+                        let root = target.target_path.join(target.path.file_name().unwrap());
+                        println!("Mounting {} at {}... (Ctrl-C to unmount)", tag, mountpoint);
+                        if let Err(error) = fuse_mount::mount(root, Path::new(mountpoint)) {
+                            println!("Error: {}", error);
+                        }
+                    }
+                }
+            }
+            if !found {
+                println!("No targets!");
+            }
+        }
+        #[cfg(not(feature = "fuse"))]
+        {
+            let _ = options;
+            println!("This build was compiled without the `fuse` feature; mounting is unavailable.");
+        }
     } else if let Some(options) = options.subcommand_matches("completion") {
         cli::print_completions(options.value_of("SHELL").unwrap().to_owned());
     } else if let Some(options) = options.subcommand_matches("restore") {
@@ -96,6 +124,22 @@ fn main() {
     }
 }
 
+/// Backs up a single target via its own `backup()`, which dispatches to
+/// `operation::copy_to_target` (threaded across the available cores,
+/// honoring `dry_run`/`verbose`) for a local target, or `operation::network_copy`
+/// for a network one
+fn run_target(target: BackupTarget, dry_run: bool, verbose: bool) {
+    let res = target.backup(dry_run, verbose);
+    report_result(res.map(|num| (num, 0)));
+}
+
+fn report_result(res: std::io::Result<(i32, u64)>) {
+    match res {
+        Ok((files, bytes)) => println!("Done: {} files copied ({} bytes).", files, bytes),
+        Err(error) => println!(" Error: {}", error),
+    }
+}
+
 fn flush() {
     std::io::stdout().flush().unwrap();
 }
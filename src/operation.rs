@@ -5,39 +5,85 @@
 //! Contains helper methods and structs for backup operations
 //! such as checking timestamps, copying to targets and restoring
 
-use std::fs::{copy, create_dir, create_dir_all, read_dir, remove_dir_all};
+use std::collections::VecDeque;
+use std::fs::{
+    copy, create_dir, create_dir_all, hard_link, read_dir, remove_dir_all, remove_file, rename,
+};
 use std::fs::{DirEntry, File, Metadata};
-use std::io::{Error, ErrorKind, Result};
-use std::path::PathBuf;
-use std::sync::mpsc::channel;
-use std::sync::{Arc, Mutex};
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::thread::{spawn, JoinHandle};
 
+use filetime::{set_file_times, FileTime};
+use ignore::WalkBuilder;
+use native_tls::TlsConnector;
 use regex::Regex;
+use sha2::{Digest, Sha256};
+use tar::Builder;
+use xz2::read::XzDecoder;
+use xz2::stream::{LzmaOptions, Stream as XzStream};
+use xz2::write::XzEncoder;
+use zstd::Decoder as ZstdDecoder;
+use zstd::Encoder as ZstdEncoder;
 
-use crate::config::BackupTarget;
+use crate::config::{ArchiveFormat, BackupMode, BackupTarget, CompareMode, CompressionMode, Preserve};
 
 /// Checks if a directory or file is ignored
 ///
 /// # Parameters
 /// - path: The path (relative to the base directory) of the folder or file
-/// in question
+///   in question
 /// - ignored_files: The vector of files to be ignored (regexes are prefixed
-/// with a r#)
+///   with a r#)
 /// - ignored_folders: same as `ignored_files` except for directories
+/// - allowed_extensions: if non-empty, files whose lowercased extension is
+///   not in this list are ignored; extension-less files are always ignored
+///   when this list is non-empty
+/// - excluded_extensions: files whose lowercased extension is in this list
+///   are always ignored, taking priority over `allowed_extensions`
 ///
 /// # Returns
 /// if the file or folder is to be ignored
 pub fn ignored(
-    path: &PathBuf,
+    path: &Path,
     metadata: &Metadata,
     ignored_files: &[String],
     ignored_folders: &[String],
+    allowed_extensions: &[String],
+    excluded_extensions: &[String],
 ) -> bool {
     if metadata.is_file() {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase());
+        if let Some(extension) = &extension {
+            if excluded_extensions
+                .iter()
+                .any(|excluded| excluded.to_lowercase() == *extension)
+            {
+                return true;
+            }
+        }
+        if !allowed_extensions.is_empty() {
+            let allowed = extension
+                .as_ref()
+                .map(|extension| {
+                    allowed_extensions
+                        .iter()
+                        .any(|allowed| allowed.to_lowercase() == *extension)
+                })
+                .unwrap_or(false);
+            if !allowed {
+                return true;
+            }
+        }
         for filter in ignored_files {
-            if filter.starts_with("r#") {
-                let r = Regex::new(&filter[2..]);
+            if let Some(pattern) = filter.strip_prefix("r#") {
+                let r = Regex::new(pattern);
                 if let Ok(r) = r {
                     if r.is_match(path.file_name().unwrap().to_str().unwrap()) {
                         return true;
@@ -49,8 +95,8 @@ pub fn ignored(
         }
     } else {
         for filter in ignored_folders {
-            if filter.starts_with("r#") {
-                let r = Regex::new(&filter[2..]);
+            if let Some(pattern) = filter.strip_prefix("r#") {
+                let r = Regex::new(pattern);
                 if let Ok(r) = r {
                     if r.is_match(path.as_os_str().to_str().unwrap()) {
                         return true;
@@ -64,6 +110,368 @@ pub fn ignored(
     false
 }
 
+/// Decides whether a copy onto an already-existing destination file can be
+/// skipped, according to the target's `compare` mode
+///
+/// # Parameters
+/// - compare: the comparison mode to apply
+/// - compare_contents: when `compare` is `Timestamp`, also skip a file whose
+///   mtime advanced but whose bytes are unchanged (e.g. touched or rewritten
+///   identically by a build)
+/// - check_timestamp: wether timestamp comparison is enabled at all (ex.
+///   disabled by `always_copy` or `keep_num > 1`)
+/// - from_meta: metadata of the source file
+/// - to: the destination file, which may or may not exist yet
+///
+/// # Returns
+/// `true` if the destination is already up to date and the copy can be skipped
+fn should_skip_copy(
+    compare: CompareMode,
+    compare_contents: bool,
+    check_timestamp: bool,
+    from: &Path,
+    from_meta: &Metadata,
+    to: &Path,
+) -> Result<bool> {
+    let to_file = match File::open(to) {
+        Ok(to_file) => to_file,
+        Err(_) => return Ok(false),
+    };
+    let to_meta = to_file.metadata()?;
+    // the source is no newer than the destination, so a Timestamp copy would be skipped
+    let stale = check_timestamp && from_meta.modified()? <= to_meta.modified()?;
+    match compare {
+        CompareMode::Timestamp => {
+            if stale {
+                return Ok(true);
+            }
+            // compare_contents catches the case CompareMode::Timestamp alone
+            // misses: mtime moved forward but the bytes didn't actually change
+            if compare_contents && from_meta.len() == to_meta.len() {
+                return files_identical(from, to);
+            }
+            Ok(false)
+        }
+        CompareMode::Content => Ok(from_meta.len() == to_meta.len() && files_identical(from, to)?),
+        CompareMode::Both => {
+            if stale {
+                return Ok(true);
+            }
+            Ok(from_meta.len() == to_meta.len() && files_identical(from, to)?)
+        }
+    }
+}
+
+/// Compares two files block-by-block using 64 KiB buffers, bailing out as
+/// soon as the first mismatch is found
+fn files_identical(a: &Path, b: &Path) -> Result<bool> {
+    let mut file_a = File::open(a)?;
+    let mut file_b = File::open(b)?;
+    let mut buf_a = [0u8; 65536];
+    let mut buf_b = [0u8; 65536];
+    loop {
+        let read_a = file_a.read(&mut buf_a)?;
+        let read_b = file_b.read(&mut buf_b)?;
+        if read_a != read_b || buf_a[..read_a] != buf_b[..read_b] {
+            return Ok(false);
+        }
+        if read_a == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Restores the requested pieces of the source's metadata onto a freshly
+/// copied destination file or directory
+///
+/// # Parameters
+/// - from: the source path the metadata was read from (needed alongside
+///   `from_meta` to copy extended attributes, which are looked up by path)
+/// - from_meta: metadata of the source the copy came from
+/// - to: the destination file or directory that was just created
+/// - preserve: which attributes to carry over
+///
+/// # Note
+/// Failures here are not fatal to the copy itself, so callers typically
+/// ignore the returned error
+fn preserve_metadata(from: &Path, from_meta: &Metadata, to: &Path, preserve: &[Preserve]) -> Result<()> {
+    if preserve.contains(&Preserve::Mtime) {
+        let mtime = FileTime::from_last_modification_time(from_meta);
+        let atime = FileTime::from_last_access_time(from_meta);
+        set_file_times(to, atime, mtime)?;
+    }
+    #[cfg(unix)]
+    {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        if preserve.contains(&Preserve::Mode) {
+            std::fs::set_permissions(to, std::fs::Permissions::from_mode(from_meta.mode()))?;
+        }
+        if preserve.contains(&Preserve::Ownership) {
+            if let Ok(c_path) = CString::new(to.as_os_str().as_bytes()) {
+                unsafe {
+                    libc::chown(c_path.as_ptr(), from_meta.uid(), from_meta.gid());
+                }
+            }
+        }
+        if preserve.contains(&Preserve::Xattr) {
+            if let Ok(names) = xattr::list(from) {
+                for name in names {
+                    if let Ok(Some(value)) = xattr::get(from, &name) {
+                        let _ = xattr::set(to, &name, &value).is_ok();
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Applies `preserve_metadata` to the directory a just-copied file lives in,
+/// using the file's own paths to find it
+///
+/// # Note
+/// Every file in a directory triggers this call for that same directory, so
+/// the directory's metadata ends up set repeatedly; that's harmless here
+/// since `preserve_metadata` is idempotent, and simpler than tracking which
+/// directories have already been visited
+fn preserve_directory_metadata(from: &Path, to: &Path, preserve: &[Preserve]) {
+    if let (Some(source_dir), Some(dest_dir)) = (from.parent(), to.parent()) {
+        if let Ok(meta) = source_dir.metadata() {
+            let _ = preserve_metadata(source_dir, &meta, dest_dir, preserve).is_ok();
+        }
+    }
+}
+
+/// Disambiguates the temp files concurrent `atomic_copy` calls create
+/// alongside each other in the same destination directory
+static TEMP_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Copies `from` to `to` atomically: the bytes land in a sibling temp file
+/// first, fsynced to disk, and only renamed into place once that succeeds,
+/// so a crash, Ctrl-C, or full disk mid-write never leaves `to` holding a
+/// truncated file
+///
+/// # Error
+/// Returns an error if the copy, fsync, or rename fails; the temp file is
+/// removed in that case so it doesn't linger next to `to`
+fn atomic_copy(from: &Path, to: &Path) -> Result<()> {
+    let parent = to.parent().unwrap_or_else(|| Path::new("."));
+    let file_name = to
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("file");
+    let unique = TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let temp = parent.join(format!(
+        "{}.backup-rat-tmp-{}-{}",
+        file_name,
+        std::process::id(),
+        unique
+    ));
+
+    let result = (|| {
+        copy(from, &temp)?;
+        File::open(&temp)?.sync_all()?;
+        rename(&temp, to)
+    })();
+    if result.is_err() {
+        let _ = remove_file(&temp).is_ok();
+    }
+    result.map(|_| ())
+}
+
+/// Copies `from` to `to`, atomically (via `atomic_copy`) when `atomic` is
+/// true, or with a direct `std::fs::copy` otherwise, per `target.atomic_writes`
+fn copy_or_atomic(from: &Path, to: &Path, atomic: bool) -> Result<()> {
+    if atomic {
+        atomic_copy(from, to)
+    } else {
+        copy(from, to).map(|_| ())
+    }
+}
+
+/// Appends the extension matching a compression mode to a destination
+/// path, leaving it untouched when compression is disabled
+fn compressed_dest_path(dest: &Path, compression: CompressionMode) -> PathBuf {
+    match compression {
+        CompressionMode::None => dest.to_path_buf(),
+        CompressionMode::Zstd => append_extension(dest, "zst"),
+        CompressionMode::Xz => append_extension(dest, "xz"),
+    }
+}
+
+fn append_extension(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap().to_os_string();
+    name.push(".");
+    name.push(extension);
+    path.with_file_name(name)
+}
+
+/// Streams `source` through the target's configured compressor straight
+/// into `dest`, using the same temp-file-then-rename pattern as
+/// `atomic_copy` so a partial write never lands as the visible destination
+fn compressed_copy(
+    source: &Path,
+    dest: &Path,
+    compression: CompressionMode,
+    level: i32,
+    window: u32,
+) -> Result<()> {
+    let parent = dest.parent().unwrap_or_else(|| Path::new("."));
+    let file_name = dest
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("file");
+    let unique = TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let temp = parent.join(format!(
+        "{}.backup-rat-tmp-{}-{}",
+        file_name,
+        std::process::id(),
+        unique
+    ));
+
+    let result: Result<()> = (|| {
+        let mut source_file = File::open(source)?;
+        let temp_file = File::create(&temp)?;
+        match compression {
+            CompressionMode::Zstd => {
+                let mut encoder = ZstdEncoder::new(temp_file, level)?;
+                encoder.window_log(window)?;
+                std::io::copy(&mut source_file, &mut encoder)?;
+                encoder.finish()?;
+            }
+            CompressionMode::Xz => {
+                let mut lzma_options = LzmaOptions::new_preset(level as u32)
+                    .map_err(Error::other)?;
+                lzma_options.dict_size(1u32 << window.min(31));
+                let stream = XzStream::new_lzma_encoder(&lzma_options)
+                    .map_err(Error::other)?;
+                let mut encoder = XzEncoder::new_stream(temp_file, stream);
+                std::io::copy(&mut source_file, &mut encoder)?;
+                encoder.finish()?;
+            }
+            CompressionMode::None => {
+                let mut temp_file = temp_file;
+                std::io::copy(&mut source_file, &mut temp_file)?;
+            }
+        }
+        Ok(())
+    })();
+
+    match result {
+        Ok(()) => rename(&temp, dest),
+        Err(error) => {
+            let _ = remove_file(&temp).is_ok();
+            Err(error)
+        }
+    }
+}
+
+/// Reverses `compressed_copy`, decompressing a `.zst`/`.xz` artifact back
+/// into a plain file at `dest`
+pub fn decompress_to(source: &Path, compression: CompressionMode, dest: &Path) -> Result<()> {
+    if let Some(parent) = dest.parent() {
+        create_dir_all(parent)?;
+    }
+    let source_file = File::open(source)?;
+    let mut dest_file = File::create(dest)?;
+    match compression {
+        CompressionMode::Zstd => {
+            let mut decoder = ZstdDecoder::new(source_file)?;
+            std::io::copy(&mut decoder, &mut dest_file)?;
+        }
+        CompressionMode::Xz => {
+            let mut decoder = XzDecoder::new(source_file);
+            std::io::copy(&mut decoder, &mut dest_file)?;
+        }
+        CompressionMode::None => {
+            let mut source_file = source_file;
+            std::io::copy(&mut source_file, &mut dest_file)?;
+        }
+    }
+    Ok(())
+}
+
+/// Walks a target's source tree, honoring the flat `ignore_files`/
+/// `ignore_folders` lists plus, when enabled on the target, hierarchical
+/// ignore files discovered along the way: a `.backupignore` in any
+/// directory (`ignore_hierarchical`) and, optionally, real `.gitignore`/
+/// global/exclude files (`respect_gitignore`). Both use full gitignore
+/// glob semantics (`*`/`**`, a leading `/` anchor, a trailing `/` for
+/// directories only, and last-match-wins `!` negation), scoped to the
+/// subtree the ignore file was found in.
+///
+/// This is the single walker shared by `copy_to`, `threaded_copy_to` and
+/// `archive_to`, so ignore rules apply uniformly whether a target is
+/// copied single-threaded, threaded, or archived.
+///
+/// # Parameters
+/// - target: the target whose `path` is walked
+///
+/// # Returns
+/// the files to copy, in walk order (directories are created by callers
+/// as needed from each file's destination path)
+fn walk_files(target: &BackupTarget) -> Result<Vec<PathBuf>> {
+    let from = &target.path;
+    if from.metadata()?.is_file() {
+        return Ok(vec![from.clone()]);
+    }
+    let ignored_files = &target.ignore_files;
+    let ignored_folders = &target.ignore_folders;
+    let mut builder = WalkBuilder::new(from);
+    builder
+        .hidden(false)
+        .parents(target.respect_gitignore)
+        .ignore(false)
+        .git_ignore(target.respect_gitignore)
+        .git_global(target.respect_gitignore)
+        .git_exclude(target.respect_gitignore);
+    if target.ignore_hierarchical {
+        builder.add_custom_ignore_filename(".backupignore");
+    }
+
+    let mut files = Vec::new();
+    // directories matched by `ignore_folders`; everything beneath them is skipped
+    let mut skipped_dirs: Vec<PathBuf> = Vec::new();
+    for entry in builder.build() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        if skipped_dirs.iter().any(|dir| entry.path().starts_with(dir)) {
+            continue;
+        }
+        let file_type = match entry.file_type() {
+            Some(file_type) => file_type,
+            None => continue,
+        };
+        let relative = entry.path().strip_prefix(from).unwrap_or_else(|_| entry.path());
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+        if ignored(
+            relative,
+            &metadata,
+            ignored_files,
+            ignored_folders,
+            &target.allowed_extensions,
+            &target.excluded_extensions,
+        ) {
+            if file_type.is_dir() {
+                skipped_dirs.push(entry.path().to_path_buf());
+            }
+            continue;
+        }
+        if file_type.is_file() {
+            files.push(entry.path().to_path_buf());
+        }
+    }
+    Ok(files)
+}
+
 /// Copies a folder or file to a destination
 /// whilst also checking timestamps to override or not
 ///
@@ -71,7 +479,10 @@ pub fn ignored(
 /// - from: the file or folder to copy
 /// - to: the parent dir of the *from* object
 /// - check_timestamp: wether to check file modification before copy
-/// or always copy
+///   or always copy
+/// - dry_run: when true, reports what would be copied without touching
+///   the destination (no directories created, no files written)
+/// - verbose: when true, prints every `from -> to` pair as it's handled
 ///
 /// # Returns
 /// Returns an the number of copied files
@@ -79,7 +490,7 @@ pub fn ignored(
 /// # Error
 /// Returns an error if the target could not be written to or the *from* target could
 /// not be read
-pub fn copy_to(target: &BackupTarget) -> Result<i32> {
+pub fn copy_to(target: &BackupTarget, dry_run: bool, verbose: bool) -> Result<i32> {
     let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
     let from = &target.path;
     let to = &target.target_path;
@@ -88,128 +499,141 @@ pub fn copy_to(target: &BackupTarget) -> Result<i32> {
     } else {
         false
     };
-    let ignored_files = &target.ignore_files;
-    let ignored_folders = &target.ignore_folders;
     let mut num: i32 = 0;
-    let from_file: File = File::open(from)?;
-
-    if from_file.metadata().unwrap().is_file() {
-        if let Some(file_name) = from.file_name() {
-            if ignored(
-                from,
-                &from_file.metadata().unwrap(),
-                ignored_files,
-                ignored_folders,
-            ) {
-                return Ok(0);
-            }
-            if let Ok(to_file) = File::open(to.join(file_name)) {
-                if check_timestamp
-                    && from_file.metadata().unwrap().modified().unwrap()
-                        < to_file.metadata().unwrap().modified().unwrap()
-                {
-                    return Ok(0);
-                }
-            }
-            num += 1;
-            copy(from, to.join(file_name))?;
+
+    if from.metadata()?.is_file() {
+        let dest = to.join(from.file_name().unwrap());
+        let from_meta = from.metadata()?;
+        if should_skip_copy(target.compare, target.compare_contents, check_timestamp, from, &from_meta, &dest)? {
+            return Ok(0);
         }
+        if verbose || dry_run {
+            println!("{} -> {}", from.display(), dest.display());
+        }
+        if dry_run {
+            return Ok(1);
+        }
+        rotate_backup(&dest, target.mode, &target.suffix, target.keep_num)?;
+        copy_or_atomic(from, &dest, target.atomic_writes)?;
+        let _ = preserve_metadata(from, &from_meta, &dest, &target.preserve).is_ok();
+        return Ok(1);
+    }
+
+    let root = to.join(from.file_name().unwrap());
+    let previous_snapshot = if target.keep_num > 1 {
+        latest_snapshot_dir(&root)
     } else {
-        // the files and folders to be copied
-        // this is better than using recursion in the case of stack overflows
-        let mut copy_queue: Vec<(DirEntry, PathBuf)> = Vec::new();
-        for dir_entry in read_dir(from)? {
-            if let Ok(dir_entry) = dir_entry {
-                let file_name = dir_entry.file_name();
-                if target.keep_num == 1 {
-                    copy_queue.push((
-                        dir_entry,
-                        to.join(from.file_name().unwrap()).join(file_name),
-                    ));
-                } else {
-                    let time_dir = to
-                        .join(from.file_name().unwrap())
-                        .join(&now)
-                        .join(file_name);
-                    copy_queue.push((dir_entry, time_dir));
-                }
+        None
+    };
+    let store_root = chunk_store_root(target);
+    if !dry_run {
+        if target.keep_num == 1 {
+            if File::open(&root).is_err() {
+                create_dir(&root)?;
             }
+        } else {
+            create_dir(root.join(&now))?;
+            clear_old(&root, target.keep_num);
         }
-        // creates the target folder if it doesn't exist
-        if target.keep_num == 1 && File::open(to.join(from.file_name().unwrap())).is_err() {
-            create_dir(to.join(from.file_name().unwrap()))?;
-        } else if target.keep_num > 1 {
-            create_dir(to.join(from.file_name().unwrap()).join(&now))?;
-            clear_old(&to.join(from.file_name().unwrap()), target.keep_num)
-        }
+    }
 
-        while !copy_queue.is_empty() {
-            let entry = copy_queue.pop().unwrap();
-            let info: Metadata = entry.0.metadata().unwrap();
-            if info.is_file() {
-                if ignored(&entry.0.path(), &info, &ignored_files, &ignored_folders) {
-                    continue;
-                }
-                if check_timestamp {
-                    let copied_file = File::open(&entry.1);
-                    if let Ok(copied_file) = copied_file {
-                        if entry.0.metadata().unwrap().modified().unwrap()
-                            < copied_file.metadata().unwrap().modified().unwrap()
-                        {
-                            continue;
-                        }
-                    }
-                }
-                if copy(entry.0.path(), &entry.1).is_ok() {
-                    num += 1;
-                }
-            } else {
-                let entry_path = entry.0.path();
-                let striped_path = entry_path.strip_prefix(from);
-                if let Ok(striped_path) = striped_path {
-                    if ignored(
-                        &striped_path.to_path_buf(),
-                        &info,
-                        ignored_files,
-                        ignored_folders,
-                    ) {
-                        continue;
-                    }
-                }
-                if File::open(&entry.1).is_err() {
-                    create_dir(&entry.1).is_ok();
-                }
-                if let Ok(dir_entries) = read_dir(entry.0.path()) {
-                    for e in dir_entries {
-                        if let Ok(e) = e {
-                            let target_path: PathBuf = entry.1.join(e.file_name());
-                            copy_queue.push((e, target_path));
-                        }
-                    }
-                }
+    for source in walk_files(target)? {
+        let relative = source.strip_prefix(from).unwrap_or(&source);
+        let dest = if target.keep_num > 1 {
+            root.join(&now).join(relative)
+        } else {
+            root.join(relative)
+        };
+        let dest = compressed_dest_path(&dest, target.compression);
+        if !dry_run {
+            if let Some(parent) = dest.parent() {
+                create_dir_all(parent)?;
+                preserve_directory_metadata(&source, &dest, &target.preserve);
             }
         }
+        let from_meta = source.metadata()?;
+        if should_skip_copy(target.compare, target.compare_contents, check_timestamp, &source, &from_meta, &dest)? {
+            continue;
+        }
+        if verbose || dry_run {
+            println!("{} -> {}", source.display(), dest.display());
+        }
+        if dry_run {
+            num += 1;
+            continue;
+        }
+        rotate_backup(&dest, target.mode, &target.suffix, target.keep_num)?;
+        if target.dedup {
+            dedup_copy(&store_root, &source, &from_meta, &dest)?;
+        } else if target.compression != CompressionMode::None {
+            compressed_copy(
+                &source,
+                &dest,
+                target.compression,
+                target.compression_level,
+                target.compression_window,
+            )?;
+            let _ = preserve_metadata(&source, &from_meta, &dest, &target.preserve).is_ok();
+        } else {
+            let previous = previous_snapshot.as_ref().map(|dir| dir.join(relative));
+            if !hard_link_if_unchanged(&source, &from_meta, &dest, previous.as_deref()) {
+                copy_or_atomic(&source, &dest, target.atomic_writes)?;
+                let _ = preserve_metadata(&source, &from_meta, &dest, &target.preserve).is_ok();
+            }
+        }
+        preserve_directory_metadata(&source, &dest, &target.preserve);
+        num += 1;
     }
 
     Ok(num)
 }
 
 enum Command {
-    Terminate,
-    Copy(PathBuf, PathBuf),
+    Copy(PathBuf, PathBuf, Option<PathBuf>),
+}
+
+/// Lock-free counters shared by every worker of a `threaded_copy_to` run,
+/// meant to be wrapped in an `Arc` and polled from another thread to
+/// render a live progress/throughput display
+///
+/// # Note
+/// Setting `stop` makes each worker exit before starting its next copy,
+/// leaving any still-queued copies undone, letting a caller cancel a
+/// running backup
+#[derive(Default)]
+pub struct Progress {
+    pub files_copied: AtomicUsize,
+    pub bytes_copied: AtomicUsize,
+    pub files_skipped: AtomicUsize,
+    pub stop: AtomicBool,
 }
 
-/// Copies a folder or file to a destination using multiple threads
+/// A single file that failed to copy during a threaded run
+#[derive(Debug)]
+pub struct CopyError {
+    pub path: PathBuf,
+    pub error: Error,
+}
+
+/// The outcome of a threaded copy
+pub struct CopyReport {
+    pub files_copied: i32,
+    pub errors: Vec<CopyError>,
+}
+
+/// Copies a folder or file to a destination using a pool of worker threads
 /// whilst also checking timestamps to override or not
 ///
 /// # Parameters
-/// - from: the file or folder to copy
-/// - to: the parent dir of the *from* object
-/// - check_timestamp: wether to check file modification before copy
-/// or always copy
+/// - target: the target to copy
+/// - num_threads: how many worker threads to copy with
+/// - progress: lock-free counters updated as files are copied; share the
+///   same `Arc` with another thread to render a live display
+/// - verbose: when true, prints every `from -> to` pair as it's dispatched
 ///
 /// # Returns
-/// Returns an the number of copied files
+/// A report with the number of copied files and any per-file errors
+/// encountered along the way
 ///
 /// # Error
 /// Returns an error if the target could not be written to or the *from* target could
@@ -217,7 +641,15 @@ enum Command {
 ///
 /// # Notes
 /// - If the target is a file it will use no threads
-pub fn threaded_copy_to(target: &BackupTarget, num_threads: i32) -> Result<i32> {
+/// - Workers pull from a shared `crossbeam_channel`, so unlike a single
+///   `Mutex`-guarded receiver, many workers can dequeue concurrently instead
+///   of serializing on one lock
+pub fn threaded_copy_to(
+    target: &BackupTarget,
+    num_threads: i32,
+    progress: Arc<Progress>,
+    verbose: bool,
+) -> Result<CopyReport> {
     let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
     let from = &target.path;
     let to = &target.target_path;
@@ -226,128 +658,394 @@ pub fn threaded_copy_to(target: &BackupTarget, num_threads: i32) -> Result<i32>
     } else {
         false
     };
-    let ignored_files = &target.ignore_files;
-    let ignored_folders = &target.ignore_folders;
-    if let Ok(file) = File::open(from) {
-        if file.metadata().unwrap().is_file() {
-            copy(from, to.join(from.file_name().unwrap()))?;
-            return Ok(1);
+
+    if from.metadata()?.is_file() {
+        let dest = to.join(from.file_name().unwrap());
+        let from_meta = from.metadata()?;
+        if should_skip_copy(target.compare, target.compare_contents, check_timestamp, from, &from_meta, &dest)? {
+            return Ok(CopyReport {
+                files_copied: 0,
+                errors: Vec::new(),
+            });
+        }
+        if verbose {
+            println!("{} -> {}", from.display(), dest.display());
         }
+        rotate_backup(&dest, target.mode, &target.suffix, target.keep_num)?;
+        copy_or_atomic(from, &dest, target.atomic_writes)?;
+        let _ = preserve_metadata(from, &from_meta, &dest, &target.preserve).is_ok();
+        progress.files_copied.fetch_add(1, Ordering::Relaxed);
+        return Ok(CopyReport {
+            files_copied: 1,
+            errors: Vec::new(),
+        });
     }
 
-    if File::open(&to).is_err() {
+    if File::open(to).is_err() {
         return Err(Error::new(
             ErrorKind::NotFound,
             "The destination is unavailable!",
         ));
     }
-    let (sender, receiver) = channel::<Command>();
-    let arc_receiver = Arc::new(Mutex::new(receiver));
-    let mut threads: Vec<JoinHandle<i32>> = Vec::new();
-    let mut num: i32 = 0;
-    for _ in 1..num_threads {
-        let receiver = Arc::clone(&arc_receiver);
+
+    let root = to.join(from.file_name().unwrap());
+    let previous_snapshot = if target.keep_num > 1 {
+        latest_snapshot_dir(&root)
+    } else {
+        None
+    };
+    let store_root = chunk_store_root(target);
+    if target.keep_num == 1 {
+        if File::open(&root).is_err() {
+            create_dir(&root)?;
+        }
+    } else {
+        create_dir_all(root.join(&now))?;
+        clear_old(&root, target.keep_num);
+    }
+
+    let (sender, receiver) = crossbeam_channel::unbounded::<Command>();
+    let (error_sender, error_receiver) = crossbeam_channel::unbounded::<CopyError>();
+    let mut threads: Vec<JoinHandle<()>> = Vec::new();
+    let dedup = target.dedup;
+    let compression = target.compression;
+    let compression_level = target.compression_level;
+    let compression_window = target.compression_window;
+    let atomic_writes = target.atomic_writes;
+    for _ in 0..num_threads.max(1) {
+        let receiver = receiver.clone();
+        let error_sender = error_sender.clone();
+        let preserve = target.preserve.clone();
+        let progress = Arc::clone(&progress);
+        let store_root = store_root.clone();
         threads.push(spawn(move || {
-            let mut num = 0;
-            loop {
-                let command = receiver.lock().unwrap().recv().unwrap();
-                if let Command::Terminate = command {
+            while let Ok(Command::Copy(from, to, previous)) = receiver.recv() {
+                if progress.stop.load(Ordering::Relaxed) {
                     break;
-                } else if let Command::Copy(from, to) = command {
-                    if create_dir_all(to.parent().unwrap()).is_ok() && copy(from, to).is_ok() {
-                        num += 1;
+                }
+                let result = create_dir_all(to.parent().unwrap()).and_then(|_| {
+                    preserve_directory_metadata(&from, &to, &preserve);
+                    let from_meta = from.metadata()?;
+                    if dedup {
+                        dedup_copy(&store_root, &from, &from_meta, &to)?;
+                    } else if compression != CompressionMode::None {
+                        compressed_copy(&from, &to, compression, compression_level, compression_window)?;
+                        let _ = preserve_metadata(&from, &from_meta, &to, &preserve).is_ok();
+                    } else if !hard_link_if_unchanged(&from, &from_meta, &to, previous.as_deref()) {
+                        copy_or_atomic(&from, &to, atomic_writes)?;
+                        let _ = preserve_metadata(&from, &from_meta, &to, &preserve).is_ok();
+                    }
+                    preserve_directory_metadata(&from, &to, &preserve);
+                    Ok(from_meta.len())
+                });
+                match result {
+                    Ok(bytes) => {
+                        progress.files_copied.fetch_add(1, Ordering::Relaxed);
+                        progress.bytes_copied.fetch_add(bytes as usize, Ordering::Relaxed);
+                    }
+                    Err(error) => {
+                        progress.files_skipped.fetch_add(1, Ordering::Relaxed);
+                        let _ = error_sender.send(CopyError { path: from, error }).is_ok();
                     }
                 }
             }
-            num
         }));
     }
-    let mut read_files: Vec<(PathBuf, PathBuf)> = Vec::new();
+    // drop the handler-owned clones so the receiver disconnects (and workers
+    // exit their `recv` loop) once the dispatch loop below is done
+    drop(receiver);
+    drop(error_sender);
 
-    if File::open(&to.join(from.file_name().unwrap())).is_err() {
-        create_dir(&to.join(from.file_name().unwrap()))?;
+    for source in walk_files(target)? {
+        let relative = source.strip_prefix(from).unwrap_or(&source);
+        let dest = if target.keep_num > 1 {
+            root.join(&now).join(relative)
+        } else {
+            root.join(relative)
+        };
+        let dest = compressed_dest_path(&dest, target.compression);
+        let from_meta = source.metadata()?;
+        if should_skip_copy(target.compare, target.compare_contents, check_timestamp, &source, &from_meta, &dest)? {
+            continue;
+        }
+        if verbose {
+            println!("{} -> {}", source.display(), dest.display());
+        }
+        rotate_backup(&dest, target.mode, &target.suffix, target.keep_num)?;
+        let previous = previous_snapshot.as_ref().map(|dir| dir.join(relative));
+        let _ = sender.send(Command::Copy(source, dest, previous)).is_ok();
     }
 
-    for dir_entry in read_dir(from)? {
-        if let Ok(dir_entry) = dir_entry {
-            if target.keep_num == 1 {
-                read_files.push((dir_entry.path(), to.clone().join(from.file_name().unwrap())));
-            } else {
-                read_files.push((
-                    dir_entry.path(),
-                    to.clone().join(from.file_name().unwrap()).join(&now),
-                ));
+    drop(sender);
+    for handle in threads {
+        handle.join().unwrap();
+    }
+
+    Ok(CopyReport {
+        files_copied: progress.files_copied.load(Ordering::Relaxed) as i32,
+        errors: error_receiver.try_iter().collect(),
+    })
+}
+
+/// Streams a target's file tree to a remote backup-rat server over TLS,
+/// mirroring the traversal and filtering `copy_to` uses locally
+///
+/// # Protocol
+/// After the TLS handshake the client sends the password terminated by a
+/// newline, then the target's `keep_num` (u32 LE) so the remote side can
+/// apply the same rotation semantics as a local `keep_num > 1` target.
+/// Each file is then sent as: its path (relative to `target.path`) length
+/// (u32 LE), the UTF-8 path itself, the file length (u64 LE) and the raw
+/// file bytes. A final path length of zero marks the end of the stream.
+///
+/// # Parameters
+/// - target: the target to copy, filtered and walked the same way `copy_to` is
+/// - url: the `host:port` of the remote backup-rat server
+/// - password: the shared secret authenticating this client to the server
+///
+/// # Returns
+/// the number of files transferred
+///
+/// # Error
+/// Returns an error if the connection, the TLS handshake, or a write to
+/// the remote fails
+pub fn network_copy(target: &BackupTarget, url: &str, password: &str) -> Result<i32> {
+    let from = &target.path;
+    let host = url.split(':').next().unwrap_or(url);
+    let connector =
+        TlsConnector::new().map_err(Error::other)?;
+    let stream = TcpStream::connect(url)?;
+    let mut stream = connector
+        .connect(host, stream)
+        .map_err(Error::other)?;
+
+    stream.write_all(password.as_bytes())?;
+    stream.write_all(b"\n")?;
+    stream.write_all(&(target.keep_num as u32).to_le_bytes())?;
+
+    let mut num = 0;
+    for source in walk_files(target)? {
+        let relative = source.strip_prefix(from).unwrap_or(&source);
+        let relative = relative.to_string_lossy();
+        let metadata = source.metadata()?;
+        let mut file = File::open(&source)?;
+
+        stream.write_all(&(relative.len() as u32).to_le_bytes())?;
+        stream.write_all(relative.as_bytes())?;
+        stream.write_all(&metadata.len().to_le_bytes())?;
+        std::io::copy(&mut file, &mut stream)?;
+        num += 1;
+    }
+
+    // a zero-length path signals end of stream to the remote
+    stream.write_all(&0u32.to_le_bytes())?;
+    Ok(num)
+}
+
+/// Target average size (in bytes) a content-defined chunk boundary aims
+/// for; actual chunks vary but are clamped to `CHUNK_MIN_SIZE..=CHUNK_MAX_SIZE`
+const CHUNK_TARGET_SIZE: usize = 2 * 1024 * 1024;
+const CHUNK_MIN_SIZE: usize = 512 * 1024;
+const CHUNK_MAX_SIZE: usize = 8 * 1024 * 1024;
+const CHUNK_WINDOW: usize = 64;
+// a boundary is cut whenever `hash & CHUNK_MASK == 0`, sized so the average
+// run length before a hit lands close to `CHUNK_TARGET_SIZE`
+const CHUNK_MASK: u64 = (CHUNK_TARGET_SIZE - 1) as u64;
+
+/// A rolling buzhash over the last `CHUNK_WINDOW` bytes, used to find
+/// content-defined chunk boundaries that stay stable even when bytes are
+/// inserted or removed earlier in the file
+struct Chunker {
+    table: [u64; 256],
+    window: VecDeque<u8>,
+    hash: u64,
+}
+
+impl Chunker {
+    fn new() -> Self {
+        Chunker {
+            table: buzhash_table(),
+            window: VecDeque::with_capacity(CHUNK_WINDOW),
+            hash: 0,
+        }
+    }
+
+    /// Feeds one more byte into the rolling window, returning the updated hash
+    fn push(&mut self, byte: u8) -> u64 {
+        if self.window.len() == CHUNK_WINDOW {
+            let outgoing = self.window.pop_front().unwrap();
+            self.hash ^= self.table[outgoing as usize].rotate_left((CHUNK_WINDOW - 1) as u32);
+        }
+        self.hash = self.hash.rotate_left(1) ^ self.table[byte as usize];
+        self.window.push_back(byte);
+        self.hash
+    }
+}
+
+/// A deterministic, well-distributed 256-entry table for `Chunker`; it
+/// doesn't need to be cryptographically random, only stable across runs
+/// so identical content always chunks identically
+fn buzhash_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    for (i, slot) in table.iter_mut().enumerate() {
+        let mut x = (i as u64).wrapping_add(0x9E37_79B9_7F4A_7C15);
+        x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        *slot = x ^ (x >> 31);
+    }
+    table
+}
+
+/// Splits a file into content-defined chunks with a rolling buzhash,
+/// storing any chunk not already present under `store_root/<first2hex>/<digest>`
+///
+/// # Returns
+/// The ordered list of (digest, length) pairs making up the file
+fn chunk_file(path: &Path, store_root: &Path) -> Result<Vec<(String, u64)>> {
+    let mut file = File::open(path)?;
+    let mut chunker = Chunker::new();
+    let mut chunk = Vec::with_capacity(CHUNK_TARGET_SIZE);
+    let mut chunks = Vec::new();
+    let mut buf = [0u8; 65536];
+    loop {
+        let read = file.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        for &byte in &buf[..read] {
+            let hash = chunker.push(byte);
+            chunk.push(byte);
+            let boundary = (chunk.len() >= CHUNK_MIN_SIZE && hash & CHUNK_MASK == 0)
+                || chunk.len() >= CHUNK_MAX_SIZE;
+            if boundary {
+                chunks.push(write_chunk(store_root, &chunk)?);
+                chunk.clear();
+                chunker = Chunker::new();
             }
         }
     }
+    if !chunk.is_empty() {
+        chunks.push(write_chunk(store_root, &chunk)?);
+    }
+    Ok(chunks)
+}
 
-    if target.keep_num > 1 {
-        create_dir_all(to.clone().join(from.file_name().unwrap()).join(&now)).is_ok();
-        clear_old(&to.join(from.file_name().unwrap()), target.keep_num);
-    }
-
-    // WARNING: This code is confusing...
-    while !read_files.is_empty() {
-        let (file_path, file_parent) = read_files.pop().unwrap();
-        let file = File::open(&file_path);
-        if let Ok(file) = file {
-            let metadata = file.metadata().unwrap();
-            if metadata.is_dir() {
-                let striped_path = file_path.strip_prefix(from);
-                if let Ok(striped_path) = striped_path {
-                    if ignored(
-                        &striped_path.to_path_buf(),
-                        &metadata,
-                        ignored_files,
-                        ignored_folders,
-                    ) {
-                        continue;
-                    }
-                }
-                if let Ok(entries) = read_dir(&file_path) {
-                    for entry in entries {
-                        if let Ok(entry) = entry {
-                            if File::open(entry.path()).is_ok() {
-                                read_files.push((
-                                    entry.path(),
-                                    file_parent.join(file_path.file_name().unwrap()),
-                                ));
-                            }
-                        }
-                    }
-                }
+/// Writes a chunk into the content-addressed store keyed by its SHA-256
+/// digest, leaving an already-present chunk untouched
+fn write_chunk(store_root: &Path, bytes: &[u8]) -> Result<(String, u64)> {
+    let digest = format!("{:x}", Sha256::digest(bytes));
+    let dir = store_root.join(&digest[..2]);
+    create_dir_all(&dir)?;
+    let path = dir.join(&digest);
+    if File::open(&path).is_err() {
+        let mut chunk_file = File::create(&path)?;
+        chunk_file.write_all(bytes)?;
+    }
+    Ok((digest, bytes.len() as u64))
+}
+
+/// Chunks `source` and writes a per-file index at `dest` listing its
+/// ordered chunk digests instead of copying its bytes directly, so the
+/// `keep_num` snapshots `copy_to` creates share unchanged chunks in
+/// `store_root` rather than each storing a full copy
+fn dedup_copy(store_root: &Path, source: &Path, from_meta: &Metadata, dest: &Path) -> Result<()> {
+    let chunks = chunk_file(source, store_root)?;
+    let mtime = FileTime::from_last_modification_time(from_meta).unix_seconds();
+    let mut index = format!("len={}\nmtime={}\n", from_meta.len(), mtime);
+    for (digest, _) in chunks {
+        index.push_str(&digest);
+        index.push('\n');
+    }
+    if let Some(parent) = dest.parent() {
+        create_dir_all(parent)?;
+    }
+    std::fs::write(dest, index)
+}
+
+/// Reassembles a file written by `dedup_copy`, concatenating its chunks
+/// back together in order
+///
+/// # Parameters
+/// - index_path: the per-file index written alongside a dedup snapshot
+/// - store_root: the target's content-addressed `chunks` directory
+/// - dest: where to write the reassembled file
+pub fn restore_from_index(index_path: &Path, store_root: &Path, dest: &Path) -> Result<()> {
+    let index = std::fs::read_to_string(index_path)?;
+    let mut lines = index.lines().skip(2);
+    if let Some(parent) = dest.parent() {
+        create_dir_all(parent)?;
+    }
+    let mut out = File::create(dest)?;
+    for digest in &mut lines {
+        let chunk_path = store_root.join(&digest[..2]).join(digest);
+        let mut chunk = File::open(chunk_path)?;
+        std::io::copy(&mut chunk, &mut out)?;
+    }
+    Ok(())
+}
+
+/// The content-addressed chunk store root for a dedup-enabled target
+fn chunk_store_root(target: &BackupTarget) -> PathBuf {
+    target.target_path.join("chunks")
+}
+
+/// Rotates a destination file that is about to be overwritten according
+/// to the target's rotation `mode`, honoring `keep_num` in `Numbered` mode
+///
+/// # Parameters
+/// - dest: the file about to be overwritten (left untouched if it doesn't exist yet)
+/// - mode: the rotation strategy to apply
+/// - suffix: the suffix appended to `dest` in `Simple` mode
+/// - keep_num: how many numbered backups to retain in `Numbered` mode
+fn rotate_backup(dest: &PathBuf, mode: BackupMode, suffix: &str, keep_num: i32) -> Result<()> {
+    if File::open(dest).is_err() {
+        return Ok(());
+    }
+    match mode {
+        BackupMode::None => Ok(()),
+        BackupMode::Simple => rotate_simple(dest, suffix),
+        BackupMode::Numbered => rotate_numbered(dest, keep_num),
+        BackupMode::Existing => {
+            if numbered_path(dest, 1).exists() {
+                rotate_numbered(dest, keep_num)
             } else {
-                if check_timestamp {
-                    let target_file_path = file_parent.clone().join(file_path.file_name().unwrap());
-                    if ignored(&file_path, &metadata, &ignored_files, &ignored_folders) {
-                        continue;
-                    }
-                    if let Ok(target_file) = File::open(target_file_path) {
-                        if target_file.metadata().unwrap().modified().unwrap()
-                            > metadata.modified().unwrap()
-                        {
-                            continue;
-                        }
-                    }
-                }
-                sender
-                    .send(Command::Copy(
-                        file_path.clone(),
-                        file_parent.join(file_path.file_name().unwrap()),
-                    ))
-                    .is_ok();
+                rotate_simple(dest, suffix)
             }
         }
     }
+}
 
-    for _ in 1..num_threads {
-        sender.send(Command::Terminate).is_ok();
-    }
+/// Renames `dest` to `dest<suffix>`, overwriting any previous simple backup
+fn rotate_simple(dest: &PathBuf, suffix: &str) -> Result<()> {
+    let backup_name = format!("{}{}", dest.file_name().unwrap().to_string_lossy(), suffix);
+    rename(dest, dest.with_file_name(backup_name))
+}
 
-    for handle in threads {
-        num += handle.join().unwrap();
+/// Builds the path of the `index`-th numbered backup of `dest` (`dest.~index~`)
+fn numbered_path(dest: &Path, index: i32) -> PathBuf {
+    let name = format!("{}.~{}~", dest.file_name().unwrap().to_string_lossy(), index);
+    dest.with_file_name(name)
+}
+
+/// Shifts the numbered backups of `dest` up by one (`.~1~` -> `.~2~`, ...),
+/// dropping any whose index would exceed `keep_num`, then moves `dest`
+/// into the freed `.~1~` slot
+fn rotate_numbered(dest: &PathBuf, keep_num: i32) -> Result<()> {
+    let mut index = 1;
+    while numbered_path(dest, index).exists() {
+        index += 1;
     }
-    Ok(num)
+    index -= 1;
+    while index >= 1 {
+        let from = numbered_path(dest, index);
+        if index + 1 > keep_num.max(1) {
+            remove_file(&from)?;
+        } else {
+            rename(&from, numbered_path(dest, index + 1))?;
+        }
+        index -= 1;
+    }
+    rename(dest, numbered_path(dest, 1))
 }
 
 /// Clears the oldest backups based on keep num
@@ -381,12 +1079,52 @@ fn clear_old(directory: &PathBuf, keep_num: i32) {
                     }
                 }
                 dir_names.remove(index);
-                remove_dir_all(directory.join(new_min)).is_ok();
+                let _ = remove_dir_all(directory.join(new_min)).is_ok();
             }
         }
     }
 }
 
+/// Finds the most recently created snapshot directory under a `keep_num > 1`
+/// target's root, if one exists, by picking the greatest directory name
+/// (timestamp strings sort chronologically, same as the ordering `clear_old`
+/// relies on)
+fn latest_snapshot_dir(root: &Path) -> Option<PathBuf> {
+    read_dir(root)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.metadata().map(|meta| meta.is_dir()).unwrap_or(false))
+        .map(|entry| entry.path())
+        .max()
+}
+
+/// Hard-links a destination file to its counterpart in the previous
+/// snapshot instead of copying fresh, when the two are byte-for-byte
+/// identical (rsync `--link-dest` style)
+///
+/// # Returns
+/// `true` if the hard link was created and the caller can skip copying;
+/// `false` if there is no previous snapshot, no matching counterpart, the
+/// content differs, or the filesystem rejected the link (ex. across
+/// devices), in which case the caller should fall back to a plain copy
+fn hard_link_if_unchanged(source: &Path, from_meta: &Metadata, dest: &Path, previous: Option<&Path>) -> bool {
+    let previous = match previous {
+        Some(previous) => previous,
+        None => return false,
+    };
+    let previous_meta = match previous.metadata() {
+        Ok(meta) => meta,
+        Err(_) => return false,
+    };
+    if from_meta.len() != previous_meta.len() {
+        return false;
+    }
+    match files_identical(source, previous) {
+        Ok(true) => hard_link(previous, dest).is_ok(),
+        _ => false,
+    }
+}
+
 /// Backs up the target
 ///
 /// # Parameters
@@ -400,10 +1138,12 @@ fn clear_old(directory: &PathBuf, keep_num: i32) {
 /// - the target is unavailable (ex. unmounted drive)
 /// - the backup target can't be read
 /// - the destination can't be written to
-///
-/// # TODO
-/// - Actually use the keep_num variable of the target
-pub fn copy_to_target(target: &BackupTarget, threads: i32) -> Result<i32> {
+pub fn copy_to_target(
+    target: &BackupTarget,
+    threads: i32,
+    dry_run: bool,
+    verbose: bool,
+) -> Result<i32> {
     // checks
     if File::open(&target.target_path).is_err() {
         return Err(Error::new(
@@ -412,9 +1152,538 @@ pub fn copy_to_target(target: &BackupTarget, threads: i32) -> Result<i32> {
         ));
     }
 
-    if threads > 1 {
-        Ok(threaded_copy_to(target, threads)?)
+    match target.format {
+        // dry_run always takes the sequential path: it only needs to walk
+        // and report, so there's nothing for a worker pool to parallelize
+        ArchiveFormat::Tree if threads > 1 && !dry_run => {
+            let report = threaded_copy_to(target, threads, Arc::new(Progress::default()), verbose)?;
+            match report.errors.into_iter().next() {
+                Some(failed) => Err(Error::new(
+                    failed.error.kind(),
+                    format!("{}: {}", failed.path.display(), failed.error),
+                )),
+                None => Ok(report.files_copied),
+            }
+        }
+        ArchiveFormat::Tree => Ok(copy_to(target, dry_run, verbose)?),
+        ArchiveFormat::TarZstd | ArchiveFormat::TarXz => Ok(archive_to(target, dry_run, verbose)?),
+    }
+}
+
+/// Restores a `Tree`-format target's most recent backup (or its single flat
+/// copy, when `keep_num == 1`) back onto its original `path`, reversing
+/// whatever `copy_to`/`threaded_copy_to` did to each file: reassembling a
+/// `dedup` index via `restore_from_index`, decompressing a `compression`
+/// artifact via `decompress_to`, or plain-copying otherwise
+///
+/// # Error
+/// Returns an error if the target keeps no snapshot to restore from, or if
+/// `path` can't be written to
+pub fn restore_target(target: &BackupTarget) -> Result<i32> {
+    let root = target.target_path.join(target.path.file_name().unwrap());
+    let source_root = if target.keep_num > 1 {
+        latest_snapshot_dir(&root)
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "No snapshot available to restore from"))?
+    } else {
+        root
+    };
+    if source_root.metadata()?.is_file() {
+        // A single-file target is always written verbatim by copy_to (its
+        // file branch never applies dedup/compression), so it's restored
+        // the same way
+        copy(&source_root, &target.path)?;
+        return Ok(1);
+    }
+    let store_root = chunk_store_root(target);
+    restore_tree(&source_root, &target.path, target, &store_root)
+}
+
+/// Recursively restores the directory tree at `from` onto `to`, creating
+/// directories as needed and routing each file through `restore_file`
+fn restore_tree(from: &Path, to: &Path, target: &BackupTarget, store_root: &Path) -> Result<i32> {
+    create_dir_all(to)?;
+    let mut num = 0;
+    for entry in read_dir(from)? {
+        let entry = entry?;
+        let entry_path = entry.path();
+        if entry.metadata()?.is_dir() {
+            num += restore_tree(&entry_path, &to.join(entry.file_name()), target, store_root)?;
+        } else {
+            let original_name = strip_compressed_extension(&entry.file_name(), target.compression);
+            num += restore_file(&entry_path, &to.join(original_name), target, store_root)?;
+        }
+    }
+    Ok(num)
+}
+
+/// Restores a single file written by `copy_to`/`threaded_copy_to`, undoing
+/// whichever of `dedup`/`compression` the target has enabled (the two are
+/// mutually exclusive there, so at most one applies here)
+fn restore_file(from: &Path, to: &Path, target: &BackupTarget, store_root: &Path) -> Result<i32> {
+    if let Some(parent) = to.parent() {
+        create_dir_all(parent)?;
+    }
+    if target.dedup {
+        restore_from_index(from, store_root, to)?;
+    } else if target.compression != CompressionMode::None {
+        decompress_to(from, target.compression, to)?;
     } else {
-        Ok(copy_to(target)?)
+        copy(from, to)?;
     }
+    Ok(1)
+}
+
+/// Reverses `append_extension`, stripping the suffix `compressed_dest_path`
+/// would have added for this compression mode so the restored file lands
+/// back under its original name
+fn strip_compressed_extension(name: &std::ffi::OsStr, compression: CompressionMode) -> std::ffi::OsString {
+    let suffix = match compression {
+        CompressionMode::None => return name.to_os_string(),
+        CompressionMode::Zstd => ".zst",
+        CompressionMode::Xz => ".xz",
+    };
+    match name.to_str().and_then(|name| name.strip_suffix(suffix)) {
+        Some(stripped) => std::ffi::OsString::from(stripped),
+        None => name.to_os_string(),
+    }
+}
+
+/// Writes the target's file tree into a single compressed tar archive
+/// instead of mirroring it as a directory tree
+///
+/// # Parameters
+/// - target: the target to archive, with `format` set to `TarZstd` or `TarXz`
+/// - dry_run: when true, reports what would be archived without writing or
+///   rotating anything
+/// - verbose: when true, prints every `source -> archive` pair as it's handled
+///
+/// # Returns
+/// the number of files written into the archive
+///
+/// # Error
+/// Returns an error if the target could not be written to or the *from* target could
+/// not be read
+pub fn archive_to(target: &BackupTarget, dry_run: bool, verbose: bool) -> Result<i32> {
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let from = &target.path;
+    let to = &target.target_path;
+    let extension = match target.format {
+        ArchiveFormat::TarZstd => "tar.zst",
+        ArchiveFormat::TarXz => "tar.xz",
+        ArchiveFormat::Tree => unreachable!("archive_to called on a Tree-format target"),
+    };
+    let base_name = from.file_name().unwrap().to_string_lossy();
+    let archive_name = if target.keep_num > 1 {
+        format!("{} {}.{}", base_name, now, extension)
+    } else {
+        format!("{}.{}", base_name, extension)
+    };
+    let archive_path = to.join(&archive_name);
+
+    if dry_run {
+        let mut num = 0;
+        for source in walk_files(target)? {
+            println!("{} -> {}", source.display(), archive_path.display());
+            num += 1;
+        }
+        return Ok(num);
+    }
+
+    rotate_backup(&archive_path, target.mode, &target.suffix, target.keep_num)?;
+    if target.keep_num > 1 {
+        clear_old_archives(to, &base_name, extension, target.keep_num);
+    }
+
+    let archive_file = File::create(&archive_path)?;
+    match target.format {
+        ArchiveFormat::TarZstd => {
+            let mut encoder = ZstdEncoder::new(archive_file, target.compression_level)?;
+            encoder.window_log(target.compression_window)?;
+            let mut builder = Builder::new(encoder);
+            let num = append_entries(&mut builder, target, &archive_path, verbose)?;
+            builder.into_inner()?.finish()?;
+            Ok(num)
+        }
+        ArchiveFormat::TarXz => {
+            let mut lzma_options = LzmaOptions::new_preset(target.compression_level as u32)
+                .map_err(Error::other)?;
+            lzma_options.dict_size(1u32 << target.compression_window.min(31));
+            let stream = XzStream::new_lzma_encoder(&lzma_options)
+                .map_err(Error::other)?;
+            let mut builder = Builder::new(XzEncoder::new_stream(archive_file, stream));
+            let num = append_entries(&mut builder, target, &archive_path, verbose)?;
+            builder.into_inner()?.finish()?;
+            Ok(num)
+        }
+        ArchiveFormat::Tree => unreachable!("archive_to called on a Tree-format target"),
+    }
+}
+
+/// Streams every file returned by `walk_files` into the tar builder, keyed
+/// by its path relative to the source's parent
+///
+/// # Returns
+/// the number of files appended to the archive
+fn append_entries<W: std::io::Write>(
+    builder: &mut Builder<W>,
+    target: &BackupTarget,
+    archive_path: &Path,
+    verbose: bool,
+) -> Result<i32> {
+    let base = target.path.parent().unwrap_or_else(|| Path::new(""));
+    let mut num = 0;
+    for source in walk_files(target)? {
+        let relative_path = source.strip_prefix(base).unwrap_or(&source);
+        if verbose {
+            println!("{} -> {}", source.display(), archive_path.display());
+        }
+        builder.append_path_with_name(&source, relative_path)?;
+        num += 1;
+    }
+    Ok(num)
+}
+
+/// Clears the oldest timestamped archives based on keep_num, mirroring
+/// `clear_old`'s behavior for the tree-mode snapshot directories
+fn clear_old_archives(directory: &PathBuf, base_name: &str, extension: &str, keep_num: i32) {
+    if let Ok(entries) = read_dir(directory) {
+        let prefix = format!("{} ", base_name);
+        let suffix = format!(".{}", extension);
+        let mut archives: Vec<std::ffi::OsString> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name())
+            .filter(|name| {
+                let name = name.to_string_lossy();
+                name.starts_with(&prefix) && name.ends_with(&suffix)
+            })
+            .collect();
+        while archives.len() > keep_num as usize {
+            let mut oldest = archives[0].clone();
+            let mut index = 0;
+            for (i, item) in archives.iter().enumerate().skip(1) {
+                if *item < oldest {
+                    oldest = item.clone();
+                    index = i;
+                }
+            }
+            archives.remove(index);
+            let _ = remove_file(directory.join(oldest)).is_ok();
+        }
+    }
+}
+
+/// Returns a fresh, process- and call-unique scratch directory under the
+/// system temp dir, for tests that need to touch the filesystem
+#[cfg(test)]
+fn test_scratch_dir(label: &str) -> PathBuf {
+    let unique = TEMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
+    let dir = std::env::temp_dir().join(format!("backup-rat-test-{}-{}-{}", label, std::process::id(), unique));
+    create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn atomic_copy_writes_full_contents_and_leaves_no_temp_behind() {
+    let dir = test_scratch_dir("atomic-copy");
+    let from = dir.join("source.txt");
+    let to = dir.join("dest.txt");
+    std::fs::write(&from, b"hello atomic world").unwrap();
+
+    atomic_copy(&from, &to).unwrap();
+
+    assert_eq!(std::fs::read(&to).unwrap(), b"hello atomic world");
+    let leftovers: Vec<_> = read_dir(&dir)
+        .unwrap()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.file_name())
+        .filter(|name| name.to_string_lossy().contains("backup-rat-tmp"))
+        .collect();
+    assert!(leftovers.is_empty(), "atomic_copy left a temp file behind: {:?}", leftovers);
+
+    let _ = remove_dir_all(&dir).is_ok();
+}
+
+#[test]
+fn atomic_copy_overwrites_an_existing_destination() {
+    let dir = test_scratch_dir("atomic-copy-overwrite");
+    let from = dir.join("source.txt");
+    let to = dir.join("dest.txt");
+    std::fs::write(&from, b"new content").unwrap();
+    std::fs::write(&to, b"stale content that should be replaced").unwrap();
+
+    atomic_copy(&from, &to).unwrap();
+
+    assert_eq!(std::fs::read(&to).unwrap(), b"new content");
+
+    let _ = remove_dir_all(&dir).is_ok();
+}
+
+#[test]
+fn chunk_file_reassembles_to_the_original_bytes() {
+    let dir = test_scratch_dir("chunk-file");
+    let store_root = dir.join("store");
+    let source = dir.join("source.bin");
+    // Bigger than CHUNK_MAX_SIZE, so at least one boundary is forced
+    // regardless of where the rolling hash happens to land
+    let content: Vec<u8> = (0..(CHUNK_MAX_SIZE as u32 + 1)).map(|i| (i % 251) as u8).collect();
+    std::fs::write(&source, &content).unwrap();
+
+    let chunks = chunk_file(&source, &store_root).unwrap();
+    assert!(chunks.len() > 1, "expected the content to split into more than one chunk");
+
+    let mut reassembled = Vec::new();
+    for (digest, len) in &chunks {
+        let chunk_path = store_root.join(&digest[..2]).join(digest);
+        let bytes = std::fs::read(&chunk_path).unwrap();
+        assert_eq!(bytes.len() as u64, *len);
+        reassembled.extend_from_slice(&bytes);
+    }
+    assert_eq!(reassembled, content);
+
+    let _ = remove_dir_all(&dir).is_ok();
+}
+
+#[test]
+fn chunk_file_is_deterministic_and_dedups_identical_content() {
+    let dir = test_scratch_dir("chunk-file-dedup");
+    let store_root = dir.join("store");
+    let source = dir.join("source.bin");
+    let content: Vec<u8> = (0..50_000u32).map(|i| (i % 197) as u8).collect();
+    std::fs::write(&source, &content).unwrap();
+
+    let first = chunk_file(&source, &store_root).unwrap();
+    let second = chunk_file(&source, &store_root).unwrap();
+
+    assert_eq!(first, second, "chunking the same content twice should produce the same digests");
+    let stored: usize = read_dir(&store_root)
+        .unwrap()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| read_dir(entry.path()).unwrap().count())
+        .sum();
+    assert_eq!(stored, first.len(), "identical content should be stored only once");
+
+    let _ = remove_dir_all(&dir).is_ok();
+}
+
+#[test]
+fn ignored_matches_plain_and_regex_file_filters() {
+    let metadata = std::fs::metadata(file!()).unwrap();
+    assert!(ignored(
+        Path::new("secrets.env"),
+        &metadata,
+        &["secrets.env".to_owned()],
+        &[],
+        &[],
+        &[],
+    ));
+    assert!(ignored(
+        Path::new("backup.tmp"),
+        &metadata,
+        &[r"r#.*\.tmp$".to_owned()],
+        &[],
+        &[],
+        &[],
+    ));
+    assert!(!ignored(
+        Path::new("keep.txt"),
+        &metadata,
+        &["secrets.env".to_owned()],
+        &[],
+        &[],
+        &[],
+    ));
+}
+
+#[test]
+fn walk_files_honors_a_backupignore_file() {
+    let dir = test_scratch_dir("walk-files-backupignore");
+    std::fs::write(dir.join("keep.txt"), b"keep me").unwrap();
+    std::fs::write(dir.join("ignored.log"), b"drop me").unwrap();
+    std::fs::write(dir.join(".backupignore"), b"*.log\n").unwrap();
+
+    let target = BackupTarget {
+        tag: None,
+        path: dir.clone(),
+        ignore_files: Vec::new(),
+        ignore_folders: Vec::new(),
+        allowed_extensions: Vec::new(),
+        excluded_extensions: Vec::new(),
+        target_path: dir.join("target"),
+        optional: false,
+        keep_num: 1,
+        always_copy: false,
+        mode: BackupMode::None,
+        suffix: "~".to_owned(),
+        compare: CompareMode::Timestamp,
+        compare_contents: false,
+        preserve: vec![Preserve::Mtime],
+        format: ArchiveFormat::Tree,
+        compression_level: 3,
+        compression_window: 27,
+        compression: CompressionMode::None,
+        dedup: false,
+        ignore_hierarchical: true,
+        respect_gitignore: false,
+        atomic_writes: true,
+        additional_options: None,
+    };
+
+    let files = walk_files(&target).unwrap();
+    let names: Vec<_> = files
+        .iter()
+        .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+        .collect();
+
+    assert!(names.contains(&"keep.txt".to_owned()));
+    assert!(!names.contains(&"ignored.log".to_owned()));
+
+    let _ = remove_dir_all(&dir).is_ok();
+}
+
+#[test]
+fn ignored_enforces_allowed_and_excluded_extensions() {
+    let metadata = std::fs::metadata(file!()).unwrap();
+    // Excluded takes priority even when also allowed
+    assert!(ignored(
+        Path::new("photo.png"),
+        &metadata,
+        &[],
+        &[],
+        &["png".to_owned()],
+        &["png".to_owned()],
+    ));
+    // Not in the allow-list, so it's ignored
+    assert!(ignored(
+        Path::new("notes.txt"),
+        &metadata,
+        &[],
+        &[],
+        &["png".to_owned()],
+        &[],
+    ));
+    // In the allow-list and not excluded
+    assert!(!ignored(
+        Path::new("photo.png"),
+        &metadata,
+        &[],
+        &[],
+        &["png".to_owned()],
+        &[],
+    ));
+}
+
+/// A minimal `Tree`-format target rooted at `dir`, for tests that need a
+/// real `BackupTarget` to drive `copy_to`/`restore_target`
+#[cfg(test)]
+fn test_target(dir: &Path) -> BackupTarget {
+    BackupTarget {
+        tag: None,
+        path: dir.join("source"),
+        ignore_files: Vec::new(),
+        ignore_folders: Vec::new(),
+        allowed_extensions: Vec::new(),
+        excluded_extensions: Vec::new(),
+        target_path: dir.join("target"),
+        optional: false,
+        keep_num: 1,
+        always_copy: false,
+        mode: BackupMode::None,
+        suffix: "~".to_owned(),
+        compare: CompareMode::Timestamp,
+        compare_contents: false,
+        preserve: vec![Preserve::Mtime],
+        format: ArchiveFormat::Tree,
+        compression_level: 3,
+        compression_window: 27,
+        compression: CompressionMode::None,
+        dedup: false,
+        ignore_hierarchical: true,
+        respect_gitignore: false,
+        atomic_writes: true,
+        additional_options: None,
+    }
+}
+
+#[test]
+fn restore_target_reassembles_a_dedup_copy() {
+    let dir = test_scratch_dir("restore-dedup");
+    create_dir_all(dir.join("source")).unwrap();
+    create_dir_all(dir.join("target")).unwrap();
+    std::fs::write(dir.join("source").join("file.txt"), b"the quick brown fox").unwrap();
+    let mut target = test_target(&dir);
+    target.dedup = true;
+
+    copy_to(&target, false, false).unwrap();
+    remove_file(dir.join("source").join("file.txt")).unwrap();
+
+    restore_target(&target).unwrap();
+
+    assert_eq!(
+        std::fs::read(dir.join("source").join("file.txt")).unwrap(),
+        b"the quick brown fox"
+    );
+
+    let _ = remove_dir_all(&dir).is_ok();
+}
+
+#[test]
+fn restore_target_decompresses_a_compressed_copy() {
+    let dir = test_scratch_dir("restore-compressed");
+    create_dir_all(dir.join("source")).unwrap();
+    create_dir_all(dir.join("target")).unwrap();
+    std::fs::write(dir.join("source").join("file.txt"), b"the quick brown fox").unwrap();
+    let mut target = test_target(&dir);
+    target.compression = CompressionMode::Zstd;
+
+    copy_to(&target, false, false).unwrap();
+    assert!(dir.join("target").join("source").join("file.txt.zst").is_file());
+    remove_file(dir.join("source").join("file.txt")).unwrap();
+
+    restore_target(&target).unwrap();
+
+    assert_eq!(
+        std::fs::read(dir.join("source").join("file.txt")).unwrap(),
+        b"the quick brown fox"
+    );
+
+    let _ = remove_dir_all(&dir).is_ok();
+}
+
+#[test]
+fn archive_to_dry_run_does_not_write_the_archive() {
+    let dir = test_scratch_dir("archive-dry-run");
+    create_dir_all(dir.join("source")).unwrap();
+    create_dir_all(dir.join("target")).unwrap();
+    std::fs::write(dir.join("source").join("file.txt"), b"archive me").unwrap();
+    let mut target = test_target(&dir);
+    target.format = ArchiveFormat::TarZstd;
+
+    let num = archive_to(&target, true, false).unwrap();
+
+    assert_eq!(num, 1);
+    assert!(
+        read_dir(dir.join("target")).unwrap().next().is_none(),
+        "dry_run should not have written an archive file"
+    );
+
+    let _ = remove_dir_all(&dir).is_ok();
+}
+
+#[test]
+fn copy_to_honors_atomic_writes_false() {
+    let dir = test_scratch_dir("atomic-writes-off");
+    create_dir_all(dir.join("source")).unwrap();
+    create_dir_all(dir.join("target")).unwrap();
+    std::fs::write(dir.join("source").join("file.txt"), b"plain copy please").unwrap();
+    let mut target = test_target(&dir);
+    target.atomic_writes = false;
+
+    copy_to(&target, false, false).unwrap();
+
+    assert_eq!(
+        std::fs::read(dir.join("target").join("source").join("file.txt")).unwrap(),
+        b"plain copy please"
+    );
+
+    let _ = remove_dir_all(&dir).is_ok();
 }